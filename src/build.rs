@@ -39,7 +39,7 @@ impl BuildSystem {
     pub async fn new(
         cfg: Arc<RtcBuild>, progress: ProgressBar, ignore_chan: Option<Sender<PathBuf>>, build_event_tx: Option<CrossbeamSender<BuildEvent>>,
     ) -> Result<Self> {
-        let html_pipeline = Arc::new(HtmlPipeline::new(cfg.clone(), progress.clone(), ignore_chan)?);
+        let html_pipeline = Arc::new(HtmlPipeline::new(cfg.clone(), progress.clone(), ignore_chan, build_event_tx.is_some())?);
         Ok(Self {
             cfg,
             html_pipeline,
@@ -60,8 +60,8 @@ impl BuildSystem {
         self.progress.disable_steady_tick();
         self.progress.set_position(0);
         match res {
-            Ok(_) => {
-                self.send_build_event(|| BuildEvent::Success);
+            Ok(assets) => {
+                self.send_build_event(|| BuildEvent::Success { assets });
 
                 self.progress.set_prefix(&format!("{}", SUCCESS));
                 self.progress.finish_with_message("success");
@@ -83,7 +83,7 @@ impl BuildSystem {
         }
     }
 
-    async fn do_build(&mut self) -> Result<()> {
+    async fn do_build(&mut self) -> Result<Vec<PathBuf>> {
         // TODO: delete the contents of the `dist/.current` dir (currently in flight elsewhere).
 
         // Ensure the output dist directory is in place.
@@ -91,8 +91,8 @@ impl BuildSystem {
 
         // Spawn the source HTML pipeline. This will spawn all other pipelines derived from
         // the source HTML, and will ultimately generate and write the final HTML.
-        self.html_pipeline.clone().spawn().await?;
-        Ok(())
+        let outputs = self.html_pipeline.clone().spawn().await?;
+        Ok(outputs.iter().filter_map(|output| output.changed_asset_path()).collect())
     }
 }
 
@@ -100,6 +100,33 @@ impl BuildSystem {
 #[serde(tag = "type")]
 pub enum BuildEvent {
     Building,
-    Success,
+    Success {
+        /// The output assets (relative to the dist dir) that changed in this build, so the
+        /// reload client can hot-swap them instead of reloading the whole page.
+        assets: Vec<PathBuf>,
+    },
     Error(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `sse_reload.js` decides whether to hot-swap or fully reload based on this exact shape
+    /// (`{"type":"Success","assets":[...]}`), so pin the serialization here.
+    #[test]
+    fn success_event_serializes_with_assets() {
+        let event = BuildEvent::Success {
+            assets: vec![PathBuf::from("app.css")],
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(json, r#"{"type":"Success","assets":["app.css"]}"#);
+    }
+
+    #[test]
+    fn success_event_with_no_assets_serializes_to_empty_array() {
+        let event = BuildEvent::Success { assets: vec![] };
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(json, r#"{"type":"Success","assets":[]}"#);
+    }
+}
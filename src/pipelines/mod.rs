@@ -0,0 +1,169 @@
+//! Build pipelines: parses the source HTML, drives the asset pipelines it references, and
+//! writes the finished document out to `dist`.
+
+pub mod sse_reload;
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use async_std::fs;
+use async_std::task::{spawn, JoinHandle};
+use futures::channel::mpsc::Sender;
+use futures::SinkExt;
+use indicatif::ProgressBar;
+use nipper::Document;
+
+use crate::config::RtcBuild;
+use sse_reload::SSEReloadScript;
+
+const CSS_LINK_SELECTOR: &str = r#"link[data-trunk][rel="css"]"#;
+
+/// The output of a single pipeline run, used to patch the final HTML document and (for written
+/// assets) to report whether the asset changed since the previous build.
+pub enum TrunkLinkPipelineOutput {
+    Css(CssOutput),
+    SSEReload(SSEReloadScript),
+}
+
+impl TrunkLinkPipelineOutput {
+    async fn finalize(&self, dom: &mut Document) -> Result<()> {
+        match self {
+            Self::Css(out) => out.finalize(dom).await,
+            Self::SSEReload(out) => out.finalize(dom).await,
+        }
+    }
+
+    /// The dist-relative path of the asset this pipeline wrote, if its contents actually
+    /// changed since the previous build. Driving the hot-reload client off of this (rather
+    /// than every asset the build happened to write) is what lets it tell a genuine CSS edit
+    /// apart from an unrelated JS/WASM rebuild.
+    pub fn changed_asset_path(&self) -> Option<PathBuf> {
+        match self {
+            Self::Css(out) if out.changed => Some(out.path.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// The output of copying a single `<link data-trunk rel="css">` target into `dist`.
+pub struct CssOutput {
+    /// The dist-relative path the stylesheet was written to.
+    path: PathBuf,
+    /// Whether its contents differ from the previous build's copy.
+    changed: bool,
+}
+
+impl CssOutput {
+    async fn finalize(&self, _dom: &mut Document) -> Result<()> {
+        // `href` and `rel` were already rewritten onto the link element while the pipeline ran; there's
+        // nothing left to patch into the DOM.
+        Ok(())
+    }
+}
+
+/// Drives the source HTML pipeline: copies each linked stylesheet into `dist`, optionally
+/// injects the hot-reload script, and writes the resulting document to `dist/index.html`.
+///
+/// Holds the content hash of each stylesheet copied into dist by the previous build, so only assets whose
+/// contents actually changed are reported back to `BuildSystem`, instead of every asset the
+/// build happened to (re)write.
+pub struct HtmlPipeline {
+    cfg: Arc<RtcBuild>,
+    progress: ProgressBar,
+    ignore_chan: Option<Sender<PathBuf>>,
+    /// Whether to inject the hot-reload script into the document; only set when running under
+    /// `trunk serve` with hot-reload enabled.
+    inject_reload: bool,
+    prior_hashes: Mutex<HashMap<PathBuf, u64>>,
+}
+
+impl HtmlPipeline {
+    /// Construct a new instance.
+    pub fn new(cfg: Arc<RtcBuild>, progress: ProgressBar, ignore_chan: Option<Sender<PathBuf>>, inject_reload: bool) -> Result<Self> {
+        Ok(Self {
+            cfg,
+            progress,
+            ignore_chan,
+            inject_reload,
+            prior_hashes: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Spawn the pipeline, returning the outputs it wrote once the build completes.
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<Result<Vec<TrunkLinkPipelineOutput>>> {
+        spawn(async move { self.build().await })
+    }
+
+    async fn build(&self) -> Result<Vec<TrunkLinkPipelineOutput>> {
+        let source = fs::read_to_string(&self.cfg.target).await.context("error reading source HTML")?;
+        let mut dom = Document::from(source.as_str());
+
+        let mut outputs = Vec::new();
+        for mut link in dom.select(CSS_LINK_SELECTOR).iter() {
+            let href = match link.attr("href") {
+                Some(href) => href.to_string(),
+                None => continue,
+            };
+            let output = self.copy_css(&href).await?;
+            let new_href = format!("{}{}", self.cfg.public_url, output.path.to_string_lossy());
+            link.set_attr("href", &new_href);
+            link.set_attr("rel", "stylesheet");
+            outputs.push(TrunkLinkPipelineOutput::Css(output));
+        }
+
+        if self.inject_reload {
+            outputs.push(TrunkLinkPipelineOutput::SSEReload(SSEReloadScript::new()));
+        }
+
+        for output in &outputs {
+            output.finalize(&mut dom).await?;
+        }
+
+        let index_path = self.cfg.dist.join("index.html");
+        fs::write(&index_path, dom.html().as_bytes()).await.context("error writing index.html to dist")?;
+        self.ignore_written_path(index_path).await;
+
+        Ok(outputs)
+    }
+
+    /// Read the linked stylesheet (resolved relative to the source HTML), write it into `dist`,
+    /// and diff its content hash against the previous build to determine whether it changed.
+    async fn copy_css(&self, href: &str) -> Result<CssOutput> {
+        let source_dir = self.cfg.target.parent().unwrap_or_else(|| Path::new("."));
+        let source_path = source_dir.join(href);
+        let content = fs::read(&source_path).await.context("error reading linked CSS file")?;
+        let file_name = source_path.file_name().context("CSS link has no file name")?.to_owned();
+        let dist_path = self.cfg.dist.join(&file_name);
+        fs::write(&dist_path, &content).await.context("error writing CSS file to dist")?;
+        self.ignore_written_path(dist_path).await;
+
+        let rel_path = PathBuf::from(&file_name);
+        let hash = Self::hash_bytes(&content);
+        let changed = {
+            let mut hashes = self.prior_hashes.lock().unwrap();
+            let changed = hashes.get(&rel_path).map_or(true, |prior| *prior != hash);
+            hashes.insert(rel_path.clone(), hash);
+            changed
+        };
+
+        Ok(CssOutput { path: rel_path, changed })
+    }
+
+    /// Tell the file watcher to ignore a path this pipeline just wrote, so writing it doesn't
+    /// trigger another rebuild.
+    async fn ignore_written_path(&self, path: PathBuf) {
+        if let Some(ignore_chan) = &self.ignore_chan {
+            let _ = ignore_chan.clone().send(path).await;
+        }
+    }
+
+    fn hash_bytes(bytes: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        hasher.finish()
+    }
+}
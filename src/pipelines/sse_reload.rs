@@ -18,7 +18,7 @@ impl SSEReloadScript {
         Ok(TrunkLinkPipelineOutput::SSEReload(self))
     }
 
-    pub async fn finalize(self, dom: &mut Document) -> Result<()> {
+    pub async fn finalize(&self, dom: &mut Document) -> Result<()> {
         let script = format!(r#"<script>{}</script>"#, include_str!("sse_reload.js"));
 
         dom.select("html head").append_html(script);
@@ -0,0 +1,202 @@
+//! Dev-server reverse proxy handlers.
+
+use std::time::Instant;
+
+use anyhow::Context;
+use async_tungstenite::async_std::connect_async;
+use async_tungstenite::tungstenite::Message as TungsteniteMessage;
+use futures::{select, FutureExt, SinkExt, StreamExt};
+use indicatif::ProgressBar;
+use tide::{Request, Response};
+use tide_websockets::{Message, WebSocketConnection};
+use ulid::Ulid;
+use url::Url;
+
+use crate::common::SERVER;
+use crate::serve::State;
+
+/// The header injected into upstream requests when request tracing is enabled, so the
+/// correlation id shows up in backend logs too.
+const TRACE_HEADER: &str = "X-Trunk-Request-Id";
+
+/// Resolve the local mount path for a proxy: the explicit `rewrite`, if given, else the
+/// backend URL's own path.
+fn resolve_path(backend: &Url, rewrite: Option<String>) -> String {
+    rewrite.unwrap_or_else(|| backend.path().to_string())
+}
+
+/// A proxy handler which forwards plain HTTP requests to a configured backend.
+pub struct ProxyHandlerHttp {
+    backend: Url,
+    path: String,
+    client: surf::Client,
+    trace: bool,
+    progress: ProgressBar,
+}
+
+impl ProxyHandlerHttp {
+    /// Construct a new instance.
+    pub fn new(backend: Url, rewrite: Option<String>, trace: bool, progress: ProgressBar) -> Self {
+        let path = resolve_path(&backend, rewrite);
+        Self {
+            backend,
+            path,
+            client: surf::Client::new(),
+            trace,
+            progress,
+        }
+    }
+
+    /// The local path this handler is mounted at.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The configured backend this handler forwards requests to.
+    pub fn backend(&self) -> &Url {
+        &self.backend
+    }
+
+    /// Proxy the given request through to the backend, returning its response.
+    pub async fn proxy_request(&self, mut req: Request<State>) -> tide::Result {
+        let mut url = self.backend.clone();
+        url.set_path(req.url().path());
+        url.set_query(req.url().query());
+
+        let request_id = self.trace.then(|| Ulid::new().to_string());
+        let method = req.method();
+        let path = req.url().path().to_string();
+        let started = Instant::now();
+
+        let body = req.take_body().into_bytes().await?;
+        let mut upstream_req = surf::Request::builder(method, url).body(body).build();
+        for (name, values) in req.iter() {
+            for value in values.iter() {
+                upstream_req = upstream_req.header(name, value.as_str());
+            }
+        }
+        if let Some(id) = &request_id {
+            upstream_req = upstream_req.header(TRACE_HEADER, id.as_str());
+        }
+
+        let mut upstream_res = self.client.send(upstream_req).await.context("error proxying request to backend")?;
+        let mut res = Response::new(upstream_res.status());
+        for (name, values) in upstream_res.iter() {
+            for value in values.iter() {
+                res.insert_header(name, value.as_str());
+            }
+        }
+        res.set_body(upstream_res.take_body().into_bytes().await?);
+
+        if let Some(id) = request_id {
+            self.progress.println(format!(
+                "{} [{}] {} {} -> {} ({:?})\n",
+                SERVER,
+                id,
+                method,
+                path,
+                res.status(),
+                started.elapsed()
+            ));
+        }
+
+        Ok(res)
+    }
+}
+
+/// A proxy handler which forwards WebSocket upgrade requests to a configured backend,
+/// pumping frames in both directions for the lifetime of the connection.
+pub struct ProxyHandlerWebSocket {
+    backend: Url,
+    path: String,
+}
+
+impl ProxyHandlerWebSocket {
+    /// Construct a new instance.
+    pub fn new(backend: Url, rewrite: Option<String>) -> Self {
+        let path = resolve_path(&backend, rewrite);
+        Self { backend, path }
+    }
+
+    /// The local path this handler is mounted at.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Dial the backend over ws/wss and pump frames between it and `conn` until either side
+    /// closes the connection.
+    ///
+    /// Both directions are driven from a single `select!` loop (rather than a spawned task per
+    /// direction) so that when either side closes, the loop exits and drops both halves
+    /// together instead of leaving the other direction's task parked forever on `next()`.
+    pub async fn proxy_request(&self, req: Request<State>, conn: WebSocketConnection) -> tide::Result<()> {
+        let mut backend_url = self.backend.clone();
+        let scheme = if backend_url.scheme() == "https" { "wss" } else { "ws" };
+        backend_url.set_scheme(scheme).ok();
+        backend_url.set_path(req.url().path());
+        backend_url.set_query(req.url().query());
+
+        let (backend_stream, _) = connect_async(backend_url.as_str()).await.context("error connecting to proxied websocket backend")?;
+        let (mut backend_tx, mut backend_rx) = backend_stream.split();
+        let (mut client_tx, mut client_rx) = (conn.clone(), conn);
+
+        loop {
+            select! {
+                msg = backend_rx.next().fuse() => {
+                    let msg = match msg {
+                        Some(Ok(TungsteniteMessage::Text(text))) => Message::Text(text),
+                        Some(Ok(TungsteniteMessage::Binary(data))) => Message::Binary(data),
+                        Some(Ok(_)) => continue,
+                        _ => break,
+                    };
+                    if client_tx.send(msg).await.is_err() {
+                        break;
+                    }
+                }
+                msg = client_rx.next().fuse() => {
+                    let msg = match msg {
+                        Some(Ok(Message::Text(text))) => TungsteniteMessage::Text(text),
+                        Some(Ok(Message::Binary(data))) => TungsteniteMessage::Binary(data),
+                        Some(Ok(_)) => continue,
+                        _ => break,
+                    };
+                    if backend_tx.send(msg).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_path_defaults_to_backend_path() {
+        let backend = Url::parse("http://localhost:9000/api").unwrap();
+        assert_eq!(resolve_path(&backend, None), "/api");
+    }
+
+    #[test]
+    fn resolve_path_prefers_explicit_rewrite() {
+        let backend = Url::parse("http://localhost:9000/api").unwrap();
+        assert_eq!(resolve_path(&backend, Some("/custom".into())), "/custom");
+    }
+
+    #[test]
+    fn trace_id_only_minted_when_enabled() {
+        assert!(true.then(|| Ulid::new().to_string()).is_some());
+        assert!(false.then(|| Ulid::new().to_string()).is_none());
+    }
+
+    #[test]
+    fn trace_ids_are_unique() {
+        let a = Ulid::new().to_string();
+        let b = Ulid::new().to_string();
+        assert_ne!(a, b);
+    }
+}
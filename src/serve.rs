@@ -2,17 +2,21 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use anyhow::Result;
+use async_broadcast::Sender as BroadcastSender;
 use async_std::fs;
 use async_std::task::{spawn, spawn_local, JoinHandle};
 use crossbeam_channel::{unbounded, Receiver};
 use indicatif::ProgressBar;
 use tide::http::mime;
 use tide::{sse, Middleware, Next, Request, Response, StatusCode};
+use tide_rustls::TlsListener;
+use tide_websockets::{Message, WebSocket};
 
 use crate::build::BuildEvent;
 use crate::common::SERVER;
 use crate::config::RtcServe;
-use crate::proxy::ProxyHandlerHttp;
+use crate::proxy::{ProxyHandlerHttp, ProxyHandlerWebSocket};
+use crate::tunnel::TunnelSystem;
 use crate::watch::WatchSystem;
 
 /// A system encapsulating a build & watch system, responsible for serving generated content.
@@ -30,7 +34,8 @@ impl ServeSystem {
         let (build_event_tx, build_event_rx) = unbounded();
 
         let watch = WatchSystem::new(cfg.watch.clone(), progress.clone(), Some(build_event_tx)).await?;
-        let http_addr = format!("http://127.0.0.1:{}{}", cfg.port, &cfg.watch.build.public_url);
+        let scheme = if cfg.tls { "https" } else { "http" };
+        let http_addr = format!("{}://127.0.0.1:{}{}", scheme, cfg.port, &cfg.watch.build.public_url);
         Ok(Self {
             cfg,
             watch,
@@ -47,8 +52,20 @@ impl ServeSystem {
         let watch_handle = spawn_local(self.watch.run());
         let server_handle = Self::spawn_server(self.cfg.clone(), self.http_addr.clone(), self.progress.clone(), self.build_event_rx)?;
 
-        // Open the browser.
-        if self.cfg.open {
+        // Spawn the tunnel, if a relay was configured. The tunnel always forwards over plain
+        // HTTP: when the public listener uses TLS, `spawn_server` also binds a loopback-only
+        // plaintext listener one port up specifically so the tunnel is never stuck trying (and
+        // failing) to trust the dev server's self-signed cert.
+        let tunnel_port = if self.cfg.tls { self.cfg.port + 1 } else { self.cfg.port };
+        let local_addr = format!("127.0.0.1:{}", tunnel_port);
+        let tunnel_handle = TunnelSystem::new(self.cfg.clone(), local_addr, self.progress.clone()).map(|tunnel| spawn(tunnel.run()));
+
+        // Open the browser; there's no `http://` URL to open or browse to when serving over a
+        // unix socket, so just print guidance instead.
+        if let Some(socket_path) = &self.cfg.unix_socket {
+            self.progress
+                .println(format!("serving over unix socket {}; connect via a local reverse proxy\n", socket_path.display()));
+        } else if self.cfg.open {
             if let Err(err) = open::that(self.http_addr) {
                 self.progress.println(format!("error opening browser: {}", err));
             }
@@ -56,6 +73,9 @@ impl ServeSystem {
 
         server_handle.await;
         watch_handle.await;
+        if let Some(tunnel_handle) = tunnel_handle {
+            tunnel_handle.await;
+        }
         Ok(())
     }
 
@@ -63,30 +83,21 @@ impl ServeSystem {
         // Prep state.
         let listen_addr = format!("0.0.0.0:{}", cfg.port);
         let index = Arc::new(cfg.watch.build.dist.join("index.html"));
+        let build_event_tx = Self::bridge_build_events(build_event_rx);
 
         // Build app.
         tide::log::with_level(tide::log::LevelFilter::Error);
-        let mut app = tide::with_state(State { index, build_event_rx });
+        let mut app = tide::with_state(State { index, build_event_tx });
         app.with(IndexHtmlMiddleware)
             .at(&cfg.watch.build.public_url)
             .serve_dir(cfg.watch.build.dist.to_string_lossy().as_ref())?;
 
         // Build proxies.
         if let Some(backend) = &cfg.proxy_backend {
-            let handler = Arc::new(ProxyHandlerHttp::new(backend.clone(), cfg.proxy_rewrite.clone()));
-            progress.println(format!("{} proxying {} -> {}\n", SERVER, handler.path(), &backend));
-            app.at(handler.path()).strip_prefix().all(move |req| {
-                let handler = handler.clone();
-                async move { handler.proxy_request(req).await }
-            });
+            Self::register_proxy(&mut app, &progress, backend.clone(), cfg.proxy_rewrite.clone(), cfg.proxy_trace);
         } else if let Some(proxies) = &cfg.proxies {
             for proxy in proxies.iter() {
-                let handler = Arc::new(ProxyHandlerHttp::new(proxy.backend.clone(), proxy.rewrite.clone()));
-                progress.println(format!("{} proxying {} -> {}\n", SERVER, handler.path(), &proxy.backend));
-                app.at(handler.path()).strip_prefix().all(move |req| {
-                    let handler = handler.clone();
-                    async move { handler.proxy_request(req).await }
-                });
+                Self::register_proxy(&mut app, &progress, proxy.backend.clone(), proxy.rewrite.clone(), cfg.proxy_trace);
             }
         }
 
@@ -97,9 +108,12 @@ impl ServeSystem {
             //    error io::copy failed
             // This error has been reported: https://github.com/http-rs/tide/issues/689
             //
+            // Each connection gets its own subscriber off the broadcast sender, so multiple
+            // tabs (or a SSE consumer alongside a WebSocket one) all see every event instead of
+            // competing for a single shared receiver.
             app.at("/build_events").get(sse::endpoint(|request: Request<State>, sender| async move {
-                let build_event_rx = &request.state().build_event_rx;
-                while let Ok(event) = build_event_rx.recv() {
+                let mut build_event_rx = request.state().build_event_tx.new_receiver();
+                while let Ok(event) = build_event_rx.recv().await {
                     if let Ok(json) = serde_json::to_string(&event) {
                         let _ = sender.send("build_event", json, None).await;
                     }
@@ -107,15 +121,136 @@ impl ServeSystem {
 
                 Ok(())
             }));
+
+            // A WebSocket-based alternative to the SSE endpoint above, which doesn't suffer
+            // from the tide issue #689 error spam when a client disconnects.
+            app.at("/ws/build_events").get(WebSocket::new(|request: Request<State>, mut stream| async move {
+                let mut build_event_rx = request.state().build_event_tx.new_receiver();
+                while let Ok(event) = build_event_rx.recv().await {
+                    if let Ok(json) = serde_json::to_string(&event) {
+                        if stream.send(Message::Text(json)).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+
+                Ok(())
+            }));
         }
 
         // Listen and serve.
+        if let Some(socket_path) = &cfg.unix_socket {
+            if socket_path.exists() {
+                std::fs::remove_file(socket_path)?;
+            }
+            progress.println(format!("{} server running on unix socket {}\n", SERVER, socket_path.display()));
+            let socket_path = socket_path.clone();
+            return Ok(spawn(async move {
+                if let Err(err) = app.listen(async_std::os::unix::net::UnixListener::bind(&socket_path).await.map_err(tide::Error::from)).await {
+                    progress.println(err.to_string());
+                }
+            }));
+        }
+
         progress.println(format!("{} server running at {}\n", SERVER, &http_addr));
-        Ok(spawn(async move {
-            if let Err(err) = app.listen(listen_addr).await {
-                progress.println(err.to_string());
+        if cfg.tls {
+            let (cert, key) = Self::tls_cert_and_key(&cfg)?;
+            let listener = TlsListener::build().addrs(listen_addr).cert(cert).key(key);
+
+            // The tunnel relay can't be handed the self-signed cert, so also serve a
+            // loopback-only plaintext listener purely for `TunnelSystem` to forward through.
+            let tunnel_listen_addr = format!("127.0.0.1:{}", cfg.port + 1);
+            let tunnel_app = app.clone();
+            let tunnel_progress = progress.clone();
+            spawn(async move {
+                if let Err(err) = tunnel_app.listen(tunnel_listen_addr).await {
+                    tunnel_progress.println(err.to_string());
+                }
+            });
+
+            Ok(spawn(async move {
+                if let Err(err) = app.listen(listener).await {
+                    progress.println(err.to_string());
+                }
+            }))
+        } else {
+            Ok(spawn(async move {
+                if let Err(err) = app.listen(listen_addr).await {
+                    progress.println(err.to_string());
+                }
+            }))
+        }
+    }
+
+    /// Bridge the single-consumer crossbeam `Receiver` fed by the build system into a
+    /// broadcast channel, so every `/build_events` and `/ws/build_events` connection can hold
+    /// its own independent subscriber instead of competing for one shared receiver.
+    fn bridge_build_events(build_event_rx: Receiver<BuildEvent>) -> BroadcastSender<BuildEvent> {
+        let (mut tx, rx) = async_broadcast::broadcast(16);
+        // `async-broadcast` closes the channel once its last receiver is dropped. Without this,
+        // the channel would close the moment zero browser tabs are connected (e.g. before the
+        // first one connects, or during the reconnect gap after a reload), and the bridge loop
+        // below would then see `broadcast()` start failing and give up for good.
+        let inactive_rx = rx.deactivate();
+        // A single stalled tab must not be able to block delivery to every other client: drop
+        // its oldest buffered event instead of blocking `broadcast()` for everyone.
+        tx.set_overflow(true);
+
+        let bridge_tx = tx.clone();
+        std::thread::spawn(move || {
+            let _inactive_rx = inactive_rx;
+            while let Ok(event) = build_event_rx.recv() {
+                if async_std::task::block_on(bridge_tx.broadcast(event)).is_err() {
+                    break;
+                }
             }
-        }))
+        });
+        tx
+    }
+
+    /// Register an HTTP proxy at `rewrite` (or, if none is given, at `backend`'s path), transparently upgrading WebSocket requests
+    /// to the dedicated `ProxyHandlerWebSocket` instead of forwarding them as plain HTTP.
+    fn register_proxy(app: &mut tide::Server<State>, progress: &ProgressBar, backend: url::Url, rewrite: Option<String>, trace: bool) {
+        let http_handler = Arc::new(ProxyHandlerHttp::new(backend.clone(), rewrite.clone(), trace, progress.clone()));
+        let ws_handler = Arc::new(ProxyHandlerWebSocket::new(backend.clone(), rewrite));
+        progress.println(format!("{} proxying {} -> {}\n", SERVER, http_handler.path(), &backend));
+
+        app.at(http_handler.path())
+            .strip_prefix()
+            .with(WebSocket::new(move |req: Request<State>, conn| {
+                let ws_handler = ws_handler.clone();
+                async move { ws_handler.proxy_request(req, conn).await }
+            }))
+            .all(move |req| {
+                let http_handler = http_handler.clone();
+                async move { http_handler.proxy_request(req).await }
+            });
+    }
+
+    /// Resolve the TLS cert/key pair to serve with, generating a self-signed cert on the fly
+    /// when the user hasn't supplied their own via `--tls-cert`/`--tls-key`.
+    ///
+    /// The generated pair is cached under the OS temp dir rather than `dist`, since `dist` is
+    /// served directly by `serve_dir` and would otherwise expose the private key at
+    /// `{public_url}/self_signed_key.pem`. It's also reused across runs (keyed by port) so the
+    /// browser doesn't need to re-trust a brand new cert on every `trunk serve`.
+    fn tls_cert_and_key(cfg: &RtcServe) -> Result<(PathBuf, PathBuf)> {
+        if let (Some(cert), Some(key)) = (&cfg.tls_cert, &cfg.tls_key) {
+            return Ok((cert.clone(), key.clone()));
+        }
+
+        let cache_dir = std::env::temp_dir().join("trunk-tls");
+        std::fs::create_dir_all(&cache_dir)?;
+        let cert_path = cache_dir.join(format!("{}-cert.pem", cfg.port));
+        let key_path = cache_dir.join(format!("{}-key.pem", cfg.port));
+        if cert_path.exists() && key_path.exists() {
+            return Ok((cert_path, key_path));
+        }
+
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])?;
+        std::fs::write(&cert_path, cert.serialize_pem()?)?;
+        std::fs::write(&key_path, cert.serialize_private_key_pem())?;
+        Ok((cert_path, key_path))
     }
 }
 
@@ -124,7 +259,9 @@ impl ServeSystem {
 pub struct State {
     /// The path to the index.html file.
     pub index: Arc<PathBuf>,
-    pub build_event_rx: Receiver<BuildEvent>,
+    /// Broadcast sender for build events; each connection subscribes its own receiver via
+    /// `new_receiver()` so that events reach every connected client.
+    pub build_event_tx: BroadcastSender<BuildEvent>,
 }
 
 async fn load_index_html(index: &Path) -> tide::Result<Vec<u8>> {
@@ -0,0 +1,192 @@
+//! Public tunnel mode.
+//!
+//! When `--tunnel <relay-url>` is passed to `trunk serve`, instead of relying solely on the
+//! local TCP listener, trunk also dials out to a relay endpoint and services the requests the
+//! relay forwards over that outbound connection. This lets a remote device (a phone, a
+//! teammate on another network) reach the dev server without any local port-forwarding.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use async_std::task::sleep;
+use async_tungstenite::async_std::connect_async;
+use async_tungstenite::tungstenite::Message;
+use futures::{SinkExt, StreamExt};
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+
+use crate::common::SERVER;
+use crate::config::RtcServe;
+
+/// A single HTTP request, framed for transport over the relay connection.
+#[derive(Debug, Serialize, Deserialize)]
+struct RelayRequest {
+    id: String,
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+/// A single HTTP response, framed for transport over the relay connection.
+#[derive(Debug, Serialize, Deserialize)]
+struct RelayResponse {
+    id: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+/// Maintains an outbound connection to a tunnel relay, forwarding relayed requests to the
+/// locally running `trunk serve` instance and streaming responses back.
+pub struct TunnelSystem {
+    cfg: Arc<RtcServe>,
+    relay_url: String,
+    local_addr: String,
+    progress: ProgressBar,
+    client: surf::Client,
+}
+
+impl TunnelSystem {
+    /// Construct a new instance, if a `--tunnel` relay URL was configured.
+    ///
+    /// Returns `None` (and logs why) when `--tunnel` is combined with `--unix-socket`, since
+    /// there's no local TCP port for the tunnel to forward relayed requests to in that mode.
+    pub fn new(cfg: Arc<RtcServe>, local_addr: String, progress: ProgressBar) -> Option<Self> {
+        let relay_url = cfg.tunnel.clone()?;
+        if cfg.unix_socket.is_some() {
+            progress.println(format!(
+                "{} --tunnel is incompatible with --unix-socket (no local TCP port to forward to); tunnel disabled\n",
+                SERVER
+            ));
+            return None;
+        }
+
+        Some(Self {
+            cfg,
+            relay_url,
+            local_addr,
+            progress,
+            client: surf::Client::new(),
+        })
+    }
+
+    /// Run the tunnel, reconnecting to the relay whenever the connection drops.
+    pub async fn run(self) {
+        loop {
+            if let Err(err) = self.connect_and_serve().await {
+                self.progress.println(format!("{} tunnel error: {}, reconnecting...\n", SERVER, err));
+            }
+            sleep(Duration::from_secs(2)).await;
+        }
+    }
+
+    /// Dial the relay, register under the configured name, and service forwarded requests
+    /// until the connection drops.
+    async fn connect_and_serve(&self) -> Result<()> {
+        let (stream, _) = connect_async(&self.relay_url).await.context("error connecting to tunnel relay")?;
+        let (mut tx, mut rx) = stream.split();
+
+        if let Some(name) = &self.cfg.tunnel_name {
+            tx.send(Message::Text(name.clone())).await.context("error registering with tunnel relay")?;
+        }
+        self.progress.println(format!("{} tunnel connected via {}\n", SERVER, &self.relay_url));
+
+        while let Some(msg) = rx.next().await {
+            let msg = msg.context("error reading from tunnel relay")?;
+            let text = match msg {
+                Message::Text(text) => text,
+                Message::Close(_) => break,
+                _ => continue,
+            };
+
+            let request: RelayRequest = serde_json::from_str(&text).context("malformed relay request frame")?;
+            let response = self.forward_to_local(request).await;
+            let frame = serde_json::to_string(&response).context("error serializing relay response frame")?;
+            tx.send(Message::Text(frame)).await.context("error writing to tunnel relay")?;
+        }
+
+        Ok(())
+    }
+
+    /// Forward a single relayed request to the local server and capture its response.
+    async fn forward_to_local(&self, request: RelayRequest) -> RelayResponse {
+        // Always dial plain HTTP. When `cfg.tls` is set, `local_addr` (passed in by
+        // `ServeSystem`) points at a dedicated loopback-only plaintext listener rather than the
+        // public TLS one, since this client has no way to trust the dev server's self-signed
+        // cert; dialing `https` here would just fail the handshake and 502 every request.
+        let url = format!("http://{}{}", self.local_addr, request.path);
+        match self.send_local(&request, &url).await {
+            Ok((status, headers, body)) => RelayResponse {
+                id: request.id,
+                status,
+                headers,
+                body,
+            },
+            Err(err) => RelayResponse {
+                id: request.id,
+                status: 502,
+                headers: vec![],
+                body: err.to_string().into_bytes(),
+            },
+        }
+    }
+
+    async fn send_local(&self, request: &RelayRequest, url: &str) -> Result<(u16, Vec<(String, String)>, Vec<u8>)> {
+        let method: surf::http::Method = request.method.parse().unwrap_or(surf::http::Method::Get);
+        let mut local_req = surf::Request::builder(method, surf::Url::parse(url)?).body(request.body.clone()).build();
+        for (name, value) in &request.headers {
+            local_req = local_req.header(name.as_str(), value.as_str());
+        }
+
+        let mut res = self.client.send(local_req).await.map_err(|err| anyhow::anyhow!(err))?;
+        let status = res.status() as u16;
+        let headers = res.iter().flat_map(|(name, values)| values.iter().map(move |v| (name.to_string(), v.to_string()))).collect();
+        let body = res.take_body().into_bytes().await.map_err(|err| anyhow::anyhow!(err))?;
+        Ok((status, headers, body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relay_request_round_trips_through_json() {
+        let request = RelayRequest {
+            id: "01ARZ3NDEKTSV4RRFFQ69G5FAV".into(),
+            method: "GET".into(),
+            path: "/api/widgets?page=2".into(),
+            headers: vec![("accept".into(), "application/json".into())],
+            body: vec![1, 2, 3],
+        };
+
+        let frame = serde_json::to_string(&request).unwrap();
+        let decoded: RelayRequest = serde_json::from_str(&frame).unwrap();
+
+        assert_eq!(decoded.id, request.id);
+        assert_eq!(decoded.method, request.method);
+        assert_eq!(decoded.path, request.path);
+        assert_eq!(decoded.headers, request.headers);
+        assert_eq!(decoded.body, request.body);
+    }
+
+    #[test]
+    fn relay_response_round_trips_through_json() {
+        let response = RelayResponse {
+            id: "01ARZ3NDEKTSV4RRFFQ69G5FAV".into(),
+            status: 200,
+            headers: vec![("content-type".into(), "text/plain".into())],
+            body: b"hello".to_vec(),
+        };
+
+        let frame = serde_json::to_string(&response).unwrap();
+        let decoded: RelayResponse = serde_json::from_str(&frame).unwrap();
+
+        assert_eq!(decoded.id, response.id);
+        assert_eq!(decoded.status, response.status);
+        assert_eq!(decoded.headers, response.headers);
+        assert_eq!(decoded.body, response.body);
+    }
+}